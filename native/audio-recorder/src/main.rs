@@ -1,24 +1,75 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use hound::{SampleFormat as HoundSampleFormat, WavSpec, WavWriter};
 use serde::{Deserialize, Serialize};
-use std::io::{self, BufRead, Write};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
 use std::sync::{Arc, Mutex};
 use std::thread;
 
 use anyhow::{anyhow, Result};
 use cpal::{Sample, SampleFormat, StreamConfig};
 use dasp_sample::FromSample;
-use rubato::{FftFixedIn, Resampler};
+use rubato::{
+    FftFixedIn, Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType,
+    WindowFunction,
+};
+
+/// Shared handle to the optional WAV sink so both the audio callback and
+/// `stop_recording` (to finalize the header) can reach it.
+type SharedWavWriter = Arc<Mutex<WavWriter<BufWriter<File>>>>;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "command")]
 enum Command {
 #[serde(rename = "start")]
-Start { device_name: Option<String> },
+Start {
+    device_name: Option<String>,
+    #[serde(default)]
+    source: AudioSource,
+    target_sample_rate: Option<u32>,
+    #[serde(default)]
+    quality: ResamplerQuality,
+    vad: Option<VadConfig>,
+    record_to: Option<String>,
+},
 #[serde(rename = "stop")]
 Stop,
 #[serde(rename = "list-devices")]
 ListDevices,
 }
+
+/// Which side of the audio path to capture from. `Output` captures whatever
+/// is currently playing so it can be transcribed or recorded, the same way
+/// `Input` captures from a microphone: via the matching "monitor" input
+/// source on ALSA/PulseAudio. Not yet supported on Windows -- see
+/// `select_device_config`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum AudioSource {
+    #[default]
+    Input,
+    Output,
+}
+
+/// Which rubato resampler to use. `Fast` (`FftFixedIn`) favors low latency;
+/// `High` (`SincFixedIn`) trades latency for interpolation quality.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum ResamplerQuality {
+    #[default]
+    Fast,
+    High,
+}
+
+#[derive(Serialize)]
+struct StreamConfigMessage {
+    #[serde(rename = "type")]
+    message_type: String,
+    sample_rate: u32,
+    channels: u16,
+}
 #[derive(Serialize)]
 struct DeviceList {
 #[serde(rename = "type")]
@@ -26,9 +77,42 @@ response_type: String,
 devices: Vec<String>,
 }
 
+/// Per-request VAD tuning. `k` is the multiple of the adaptive noise floor
+/// a frame's energy must exceed to be classified as speech.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct VadConfig {
+    #[serde(default = "default_vad_k")]
+    k: f32,
+}
+
+fn default_vad_k() -> f32 {
+    3.0
+}
+
+/// Stream-lifecycle notifications raised from the cpal error callback or the
+/// resampling path, forwarded to `CommandProcessor` so it can surface them
+/// to the consumer over the framed protocol instead of just stderr.
+#[derive(Debug)]
+enum StreamEvent {
+    Error(StreamErrorKind),
+    Stopped,
+}
+
+#[derive(Debug)]
+enum StreamErrorKind {
+    DeviceDisconnected,
+    Other(String),
+}
+
 const MSG_TYPE_JSON: u8 = 1;
 const MSG_TYPE_AUDIO: u8 = 2;
 
+const VAD_FRAME_MS: usize = 30;
+const VAD_PRE_ROLL_MS: usize = 300;
+const VAD_HANGOVER_MS: usize = 500;
+const VAD_ZCR_THRESHOLD: f32 = 0.25;
+const VAD_INITIAL_NOISE_FLOOR: f32 = 1e-4;
+
 fn write_framed_message(writer: &mut impl Write, msg_type: u8, data: &[u8]) -> io::Result<()> {
     let len = data.len() as u32;
     writer.write_all(&[msg_type])?;
@@ -60,26 +144,82 @@ fn main() {
 
 struct CommandProcessor {
     cmd_rx: crossbeam_channel::Receiver<Command>,
+    stream_event_tx: crossbeam_channel::Sender<StreamEvent>,
+    stream_event_rx: crossbeam_channel::Receiver<StreamEvent>,
     active_stream: Option<cpal::Stream>,
+    active_wav_writer: Option<SharedWavWriter>,
     stdout: Arc<Mutex<io::Stdout>>,
 }
 
 impl CommandProcessor {
     fn new(cmd_rx: crossbeam_channel::Receiver<Command>, stdout: Arc<Mutex<io::Stdout>>) -> Self {
+        let (stream_event_tx, stream_event_rx) = crossbeam_channel::unbounded::<StreamEvent>();
         CommandProcessor {
             cmd_rx,
+            stream_event_tx,
+            stream_event_rx,
             active_stream: None,
+            active_wav_writer: None,
             stdout,
         }
     }
 
     fn run(&mut self) {
-        while let Ok(command) = self.cmd_rx.recv() {
-            match command {
-                Command::ListDevices => self.list_devices(),
-                Command::Start { device_name } => self.start_recording(device_name),
-                Command::Stop => self.stop_recording(),
+        loop {
+            crossbeam_channel::select! {
+                recv(self.cmd_rx) -> command => match command {
+                    Ok(Command::ListDevices) => self.list_devices(),
+                    Ok(Command::Start { device_name, source, target_sample_rate, quality, vad, record_to }) => {
+                        self.start_recording(device_name, source, target_sample_rate, quality, vad, record_to)
+                    }
+                    Ok(Command::Stop) => self.stop_recording(),
+                    Err(_) => break,
+                },
+                recv(self.stream_event_rx) -> event => {
+                    if let Ok(event) = event {
+                        self.handle_stream_event(event);
+                    }
+                },
+            }
+        }
+    }
+
+    fn handle_stream_event(&mut self, event: StreamEvent) {
+        let payload = match event {
+            StreamEvent::Error(StreamErrorKind::DeviceDisconnected) => {
+                json!({"type": "error", "kind": "device-disconnected"})
+            }
+            StreamEvent::Error(StreamErrorKind::Other(message)) => {
+                json!({"type": "error", "kind": "stream-error", "message": message})
+            }
+            StreamEvent::Stopped => {
+                self.active_stream = None;
+                self.finalize_wav_writer();
+                json!({"type": "stream-stopped"})
             }
+        };
+        if let Ok(json_string) = serde_json::to_string(&payload) {
+            let mut writer = self.stdout.lock().unwrap();
+            let _ = write_framed_message(&mut *writer, MSG_TYPE_JSON, json_string.as_bytes());
+        }
+    }
+
+    /// Finalizes and closes the WAV sink, if one is open, patching its
+    /// data-chunk length now that recording has stopped.
+    fn finalize_wav_writer(&mut self) {
+        let Some(wav_writer) = self.active_wav_writer.take() else {
+            return;
+        };
+        match Arc::try_unwrap(wav_writer) {
+            Ok(mutex) => match mutex.into_inner() {
+                Ok(writer) => {
+                    if let Err(e) = writer.finalize() {
+                        eprintln!("[audio-recorder] CRITICAL: Failed to finalize WAV file: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("[audio-recorder] CRITICAL: WAV writer mutex poisoned: {}", e),
+            },
+            Err(_) => eprintln!("[audio-recorder] CRITICAL: WAV writer still in use, could not finalize"),
         }
     }
 
@@ -99,12 +239,45 @@ impl CommandProcessor {
         }
     }
 
-    fn start_recording(&mut self, device_name: Option<String>) {
+    fn start_recording(
+        &mut self,
+        device_name: Option<String>,
+        source: AudioSource,
+        target_sample_rate: Option<u32>,
+        quality: ResamplerQuality,
+        vad: Option<VadConfig>,
+        record_to: Option<String>,
+    ) {
         self.stop_recording();
-        
-        if let Ok(stream) = start_capture(device_name, Arc::clone(&self.stdout)) {
-            if stream.play().is_ok() {
-                self.active_stream = Some(stream);
+
+        match start_capture(
+            device_name,
+            source,
+            target_sample_rate,
+            quality,
+            vad,
+            record_to,
+            Arc::clone(&self.stdout),
+            self.stream_event_tx.clone(),
+        ) {
+            Ok((stream, wav_writer, stream_config_message)) => {
+                if stream.play().is_ok() {
+                    self.active_stream = Some(stream);
+                    self.active_wav_writer = wav_writer;
+                    if let Ok(json_string) = serde_json::to_string(&stream_config_message) {
+                        let mut writer = self.stdout.lock().unwrap();
+                        let _ = write_framed_message(&mut *writer, MSG_TYPE_JSON, json_string.as_bytes());
+                    }
+                } else {
+                    let _ = self.stream_event_tx.send(StreamEvent::Error(StreamErrorKind::Other(
+                        "failed to start audio stream".to_string(),
+                    )));
+                }
+            }
+            Err(e) => {
+                let _ = self
+                    .stream_event_tx
+                    .send(StreamEvent::Error(StreamErrorKind::Other(e.to_string())));
             }
         }
     }
@@ -114,109 +287,490 @@ impl CommandProcessor {
             let _ = stream.pause();
             drop(stream);
         }
+        self.finalize_wav_writer();
+    }
+}
+
+/// Adaptive energy/zero-crossing voice activity detector. Operates on
+/// already-resampled mono samples in fixed-size frames, and decides which
+/// frames are worth streaming onward (speech, plus pre-roll and hang-over).
+struct VadState {
+    k: f32,
+    frame_samples: usize,
+    noise_floor: f32,
+    pre_roll: VecDeque<f32>,
+    pre_roll_capacity: usize,
+    hangover_frames: usize,
+    hangover_remaining: usize,
+    in_speech: bool,
+    frame_buffer: Vec<f32>,
+}
+
+impl VadState {
+    fn new(config: &VadConfig, sample_rate: u32) -> Self {
+        let frame_samples = ((sample_rate as usize * VAD_FRAME_MS) / 1000).max(1);
+        let pre_roll_capacity = (sample_rate as usize * VAD_PRE_ROLL_MS) / 1000;
+        let hangover_frames = (((sample_rate as usize * VAD_HANGOVER_MS) / 1000) / frame_samples).max(1);
+        VadState {
+            k: config.k,
+            frame_samples,
+            noise_floor: VAD_INITIAL_NOISE_FLOOR,
+            pre_roll: VecDeque::with_capacity(pre_roll_capacity),
+            pre_roll_capacity,
+            hangover_frames,
+            hangover_remaining: 0,
+            in_speech: false,
+            frame_buffer: Vec::new(),
+        }
+    }
+
+    /// Feeds newly resampled samples through the detector, returning the
+    /// samples that should actually be streamed plus any speech-start /
+    /// speech-end events that should accompany them.
+    fn process(&mut self, samples: &[f32]) -> (Vec<f32>, Vec<serde_json::Value>) {
+        self.frame_buffer.extend_from_slice(samples);
+
+        let mut out = Vec::new();
+        let mut events = Vec::new();
+
+        while self.frame_buffer.len() >= self.frame_samples {
+            let frame: Vec<f32> = self.frame_buffer.drain(..self.frame_samples).collect();
+            let energy = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+            let zcr = zero_crossing_rate(&frame);
+            let is_speech_frame = energy > self.noise_floor * self.k && zcr < VAD_ZCR_THRESHOLD;
+
+            if is_speech_frame {
+                if !self.in_speech {
+                    self.in_speech = true;
+                    events.push(json!({"type": "speech-start"}));
+                    out.extend(self.pre_roll.drain(..));
+                }
+                self.hangover_remaining = self.hangover_frames;
+                out.extend_from_slice(&frame);
+            } else {
+                self.noise_floor = 0.95 * self.noise_floor + 0.05 * energy;
+                if self.in_speech && self.hangover_remaining > 0 {
+                    self.hangover_remaining -= 1;
+                    out.extend_from_slice(&frame);
+                } else {
+                    if self.in_speech {
+                        self.in_speech = false;
+                        events.push(json!({"type": "speech-end"}));
+                    }
+                    push_pre_roll(&mut self.pre_roll, &frame, self.pre_roll_capacity);
+                }
+            }
+        }
+
+        (out, events)
     }
 }
 
+fn push_pre_roll(ring: &mut VecDeque<f32>, frame: &[f32], capacity: usize) {
+    for &s in frame {
+        if ring.len() >= capacity {
+            ring.pop_front();
+        }
+        ring.push_back(s);
+    }
+}
+
+fn zero_crossing_rate(frame: &[f32]) -> f32 {
+    if frame.len() < 2 {
+        return 0.0;
+    }
+    let crossings = frame.windows(2).filter(|w| (w[0] >= 0.0) != (w[1] >= 0.0)).count();
+    crossings as f32 / (frame.len() - 1) as f32
+}
 
-// --- MODIFIED: Function now accepts chunk_size as a parameter ---
+// --- MODIFIED: Function now accepts chunk_size, channel count, an optional VAD gate, a
+// --- stream-event sender so resampling failures reach the consumer, and an optional WAV sink ---
 fn process_and_write_data<T>(
     data: &[T],
-    resampler: &mut Option<FftFixedIn<f32>>,
+    resampler: &mut Option<Box<dyn Resampler<f32> + Send>>,
     buffer: &mut Vec<f32>,
     stdout: &Arc<Mutex<io::Stdout>>,
     chunk_size: usize,
+    vad: &mut Option<VadState>,
+    channels: usize,
+    events_tx: &crossbeam_channel::Sender<StreamEvent>,
+    wav_writer: &Option<SharedWavWriter>,
 ) where
     T: Sample,
     f32: FromSample<T>,
 {
-    let mono_samples: Vec<f32> = data.iter().map(|s| s.to_sample::<f32>()).collect();
+    let mono_samples: Vec<f32> = downmix_to_mono(data, channels);
 
     if let Some(resampler_instance) = resampler {
         buffer.extend_from_slice(&mono_samples);
-        
+
         while buffer.len() >= chunk_size {
             let chunk_to_process = buffer.drain(..chunk_size).collect::<Vec<_>>();
-            
+
             match resampler_instance.process(&[chunk_to_process], None) {
                 Ok(mut resampled) => {
                     if !resampled.is_empty() {
-                        write_audio_chunk(&resampled.remove(0), stdout);
+                        emit_audio(&resampled.remove(0), stdout, vad, wav_writer);
                     }
                 },
                 Err(e) => {
                     eprintln!("[audio-recorder] CRITICAL: Resampling failed: {}", e);
+                    let _ = events_tx.send(StreamEvent::Error(StreamErrorKind::Other(e.to_string())));
                 }
             }
         }
     } else {
-        write_audio_chunk(&mono_samples, stdout);
+        emit_audio(&mono_samples, stdout, vad, wav_writer);
     }
 }
 
+/// Averages interleaved multi-channel frames down to mono, e.g. for the
+/// typically-stereo configs exposed by output devices in loopback mode.
+fn downmix_to_mono<T>(data: &[T], channels: usize) -> Vec<f32>
+where
+    T: Sample,
+    f32: FromSample<T>,
+{
+    if channels <= 1 {
+        return data.iter().map(|s| s.to_sample::<f32>()).collect();
+    }
 
-fn write_audio_chunk(data: &[f32], stdout: &Arc<Mutex<io::Stdout>>) {
-    let mut writer = stdout.lock().unwrap();
-    let mut buffer = Vec::with_capacity(data.len() * 2);
-    for s in data {
-        buffer.extend_from_slice(&((s.clamp(-1.0, 1.0) * 32767.0) as i16).to_le_bytes());
+    data.chunks(channels)
+        .map(|frame| {
+            frame.iter().map(|s| s.to_sample::<f32>()).sum::<f32>() / channels as f32
+        })
+        .collect()
+}
+
+/// Routes resampled audio either straight to the consumer, or through the
+/// VAD gate (when enabled) so silence isn't streamed over stdout. The WAV
+/// sink, if any, always gets the full pre-VAD stream -- `record_to` is an
+/// archival copy and shouldn't silently drop non-speech segments just
+/// because the stdout stream is gated.
+fn emit_audio(samples: &[f32], stdout: &Arc<Mutex<io::Stdout>>, vad: &mut Option<VadState>, wav_writer: &Option<SharedWavWriter>) {
+    write_wav_chunk(samples, wav_writer);
+
+    if let Some(vad_state) = vad {
+        let (to_write, events) = vad_state.process(samples);
+        for event in events {
+            if let Ok(json_string) = serde_json::to_string(&event) {
+                let mut writer = stdout.lock().unwrap();
+                let _ = write_framed_message(&mut *writer, MSG_TYPE_JSON, json_string.as_bytes());
+            }
+        }
+        if !to_write.is_empty() {
+            write_stdout_audio(&to_write, stdout);
+        }
+    } else {
+        write_stdout_audio(samples, stdout);
+    }
+}
+
+fn to_i16_samples(data: &[f32]) -> Vec<i16> {
+    data.iter().map(|s| (s.clamp(-1.0, 1.0) * 32767.0) as i16).collect()
+}
+
+fn write_stdout_audio(data: &[f32], stdout: &Arc<Mutex<io::Stdout>>) {
+    let samples = to_i16_samples(data);
+    let mut buffer = Vec::with_capacity(samples.len() * 2);
+    for s in &samples {
+        buffer.extend_from_slice(&s.to_le_bytes());
     }
+    let mut writer = stdout.lock().unwrap();
     if let Err(e) = write_framed_message(&mut *writer, MSG_TYPE_AUDIO, &buffer) {
         eprintln!("[audio-recorder] CRITICAL: Failed to write to stdout: {}", e);
     }
 }
 
+fn write_wav_chunk(data: &[f32], wav_writer: &Option<SharedWavWriter>) {
+    let Some(wav) = wav_writer else {
+        return;
+    };
+    let samples = to_i16_samples(data);
+    let mut wav_writer = wav.lock().unwrap();
+    for s in &samples {
+        if let Err(e) = wav_writer.write_sample(*s) {
+            eprintln!("[audio-recorder] CRITICAL: Failed to write WAV sample: {}", e);
+        }
+    }
+}
 
-fn start_capture(device_name: Option<String>, stdout: Arc<Mutex<io::Stdout>>) -> Result<cpal::Stream> {
-    const TARGET_SAMPLE_RATE: u32 = 16000;
+
+/// Resolves the device + config to capture from for the given source.
+/// `Input` behaves as before; `Output` resolves to an ALSA/PulseAudio
+/// monitor input source on non-Windows targets, and is refused outright on
+/// Windows until a real WASAPI loopback mechanism is wired in.
+fn select_device_config(
+    host: &cpal::Host,
+    source: AudioSource,
+    device_name: &Option<String>,
+) -> Result<(cpal::Device, cpal::SupportedStreamConfig)> {
+    match source {
+        AudioSource::Input => {
+            let device = if let Some(name) = device_name {
+                if name.to_lowercase() == "default" || name.is_empty() { host.default_input_device() }
+                else { host.input_devices()?.find(|d| d.name().unwrap_or_default() == *name) }
+            } else {
+                host.default_input_device()
+            }.ok_or_else(|| anyhow!("[audio-recorder] Failed to find input device"))?;
+
+            let config = device.supported_input_configs()?
+                .find(|r| r.channels() > 0)
+                .ok_or_else(|| anyhow!("[audio-recorder] No supported input config found"))?
+                .with_max_sample_rate();
+
+            Ok((device, config))
+        }
+        // Mainline cpal does not expose WASAPI loopback capture on a playback
+        // `Device` via `build_input_stream` -- that needs either a loopback-aware
+        // host API or a separate crate, neither of which this tree pins. Rather
+        // than silently hand an output-class device to `build_input_stream` and
+        // fail confusingly deep inside `start_capture`, refuse up front until a
+        // real WASAPI loopback mechanism is wired in.
+        #[cfg(target_os = "windows")]
+        AudioSource::Output => Err(anyhow!(
+            "[audio-recorder] Output-source (loopback) capture is not yet supported on Windows"
+        )),
+        // ALSA/PulseAudio don't support opening a playback device for
+        // capture -- loopback is exposed as a "monitor" source enumerated
+        // among *input* devices instead, so `Output` has to select from
+        // `input_devices()` here rather than `output_devices()`.
+        #[cfg(not(target_os = "windows"))]
+        AudioSource::Output => {
+            let device = if let Some(name) = device_name {
+                if name.to_lowercase() == "default" || name.is_empty() { find_monitor_device(host) }
+                else { host.input_devices()?.find(|d| d.name().unwrap_or_default() == *name) }
+            } else {
+                find_monitor_device(host)
+            }.ok_or_else(|| anyhow!("[audio-recorder] Failed to find a monitor/loopback input device for output capture"))?;
+
+            let config = device.supported_input_configs()?
+                .find(|r| r.channels() > 0)
+                .ok_or_else(|| anyhow!("[audio-recorder] No supported input config found for monitor device"))?
+                .with_max_sample_rate();
+
+            Ok((device, config))
+        }
+    }
+}
+
+/// Finds the first ALSA/PulseAudio "monitor" source among input devices, used
+/// as the loopback capture device for `AudioSource::Output` on non-Windows
+/// targets, where playback devices themselves can't be opened for capture.
+#[cfg(not(target_os = "windows"))]
+fn find_monitor_device(host: &cpal::Host) -> Option<cpal::Device> {
+    host.input_devices().ok()?.find(|d| {
+        d.name()
+            .map(|name| name.to_lowercase().contains("monitor"))
+            .unwrap_or(false)
+    })
+}
+
+/// Builds the rubato resampler matching the requested quality. `Fast` uses
+/// `FftFixedIn` for low latency; `High` uses `SincFixedIn` with a windowed
+/// sinc interpolator, trading latency for interpolation quality.
+fn build_resampler(
+    quality: ResamplerQuality,
+    input_rate: u32,
+    target_rate: u32,
+    chunk_size: usize,
+) -> Result<Box<dyn Resampler<f32> + Send>> {
+    match quality {
+        ResamplerQuality::Fast => {
+            let resampler = FftFixedIn::<f32>::new(input_rate as usize, target_rate as usize, chunk_size, 1, 1)?;
+            Ok(Box::new(resampler))
+        }
+        ResamplerQuality::High => {
+            let params = SincInterpolationParameters {
+                sinc_len: 256,
+                f_cutoff: 0.95,
+                interpolation: SincInterpolationType::Linear,
+                oversampling_factor: 256,
+                window: WindowFunction::BlackmanHarris2,
+            };
+            let resampler = SincFixedIn::<f32>::new(
+                target_rate as f64 / input_rate as f64,
+                2.0,
+                params,
+                chunk_size,
+                1,
+            )?;
+            Ok(Box::new(resampler))
+        }
+    }
+}
+
+/// Classifies a cpal stream error so `err_fn` can tell the consumer whether
+/// the device went away or something else failed.
+fn classify_stream_error(err: &cpal::StreamError) -> StreamErrorKind {
+    match err {
+        cpal::StreamError::DeviceNotAvailable => StreamErrorKind::DeviceDisconnected,
+        other => StreamErrorKind::Other(other.to_string()),
+    }
+}
+
+/// Opens the `.wav` sink requested via `record_to`, sized for the resampled
+/// 16-bit mono stream that will actually be written to it.
+fn create_wav_writer(path: &str, sample_rate: u32) -> Result<SharedWavWriter> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: HoundSampleFormat::Int,
+    };
+    let writer = WavWriter::create(path, spec)?;
+    Ok(Arc::new(Mutex::new(writer)))
+}
+
+fn start_capture(
+    device_name: Option<String>,
+    source: AudioSource,
+    target_sample_rate: Option<u32>,
+    quality: ResamplerQuality,
+    vad: Option<VadConfig>,
+    record_to: Option<String>,
+    stdout: Arc<Mutex<io::Stdout>>,
+    events_tx: crossbeam_channel::Sender<StreamEvent>,
+) -> Result<(cpal::Stream, Option<SharedWavWriter>, StreamConfigMessage)> {
+    const DEFAULT_TARGET_SAMPLE_RATE: u32 = 16000;
+    const MAX_TARGET_SAMPLE_RATE: u32 = 384_000;
     const RESAMPLER_CHUNK_SIZE: usize = 1024;
-    
+
+    let target_sample_rate = target_sample_rate.unwrap_or(DEFAULT_TARGET_SAMPLE_RATE);
+    if target_sample_rate == 0 || target_sample_rate > MAX_TARGET_SAMPLE_RATE {
+        return Err(anyhow!(
+            "[audio-recorder] Invalid target_sample_rate {} (must be in 1..={})",
+            target_sample_rate,
+            MAX_TARGET_SAMPLE_RATE
+        ));
+    }
+
     let host = cpal::default_host();
-    let device = if let Some(name) = device_name {
-        if name.to_lowercase() == "default" || name.is_empty() { host.default_input_device() } 
-        else { host.input_devices()?.find(|d| d.name().unwrap_or_default() == name) }
-    } else {
-        host.default_input_device()
-    }.ok_or_else(|| anyhow!("[audio-recorder] Failed to find input device"))?;
-    
-    let config = device.supported_input_configs()?
-        .find(|r| r.channels() > 0)
-        .ok_or_else(|| anyhow!("[audio-recorder] No supported input config found"))?
-        .with_max_sample_rate();
+    let (device, config) = select_device_config(&host, source, &device_name)?;
 
     let input_sample_rate = config.sample_rate().0;
     let input_sample_format = config.sample_format();
-    
-    let mut resampler = if input_sample_rate != TARGET_SAMPLE_RATE {
-        let resampler = FftFixedIn::new(
-            input_sample_rate as usize,
-            TARGET_SAMPLE_RATE as usize,
-            RESAMPLER_CHUNK_SIZE,
-            1,
-            1,
-        )?;
-        Some(resampler)
+    let channels = config.channels() as usize;
+
+    let mut resampler = if input_sample_rate != target_sample_rate {
+        Some(build_resampler(quality, input_sample_rate, target_sample_rate, RESAMPLER_CHUNK_SIZE)?)
     } else {
         None
     };
 
-    let err_fn = |err| eprintln!("[audio-recorder] Stream error: {}", err);
+    let wav_writer = match record_to {
+        Some(path) => Some(create_wav_writer(&path, target_sample_rate)?),
+        None => None,
+    };
+    let wav_writer_for_stream = wav_writer.clone();
+
+    let events_tx_err = events_tx.clone();
+    let err_fn = move |err: cpal::StreamError| {
+        eprintln!("[audio-recorder] Stream error: {}", err);
+        let kind = classify_stream_error(&err);
+        let is_disconnect = matches!(kind, StreamErrorKind::DeviceDisconnected);
+        let _ = events_tx_err.send(StreamEvent::Error(kind));
+        if is_disconnect {
+            let _ = events_tx_err.send(StreamEvent::Stopped);
+        }
+    };
     let stream_config: StreamConfig = config.into();
-    
+
     let mut audio_buffer: Vec<f32> = Vec::new();
+    let mut vad_state = vad.as_ref().map(|cfg| VadState::new(cfg, target_sample_rate));
+
+    // Built here but only emitted by the caller once `stream.play()` has
+    // actually succeeded, so the consumer never hears about a stream config
+    // for a stream that never started.
+    let stream_config_message = StreamConfigMessage {
+        message_type: "stream-config".to_string(),
+        sample_rate: target_sample_rate,
+        channels: 1,
+    };
 
     let stream = match input_sample_format {
-        // --- MODIFIED: The callbacks now pass the known chunk size ---
+        // `build_input_stream` works unchanged for `AudioSource::Output` too:
+        // `select_device_config` already resolved `device` to the ALSA/PulseAudio
+        // monitor input device (Windows is refused before reaching here).
         SampleFormat::F32 => device.build_input_stream(&stream_config, move |data: &[f32], _| {
-            process_and_write_data(data, &mut resampler, &mut audio_buffer, &stdout, RESAMPLER_CHUNK_SIZE)
+            process_and_write_data(data, &mut resampler, &mut audio_buffer, &stdout, RESAMPLER_CHUNK_SIZE, &mut vad_state, channels, &events_tx, &wav_writer_for_stream)
         }, err_fn, None)?,
         SampleFormat::I16 => device.build_input_stream(&stream_config, move |data: &[i16], _| {
-            process_and_write_data(data, &mut resampler, &mut audio_buffer, &stdout, RESAMPLER_CHUNK_SIZE)
+            process_and_write_data(data, &mut resampler, &mut audio_buffer, &stdout, RESAMPLER_CHUNK_SIZE, &mut vad_state, channels, &events_tx, &wav_writer_for_stream)
         }, err_fn, None)?,
         SampleFormat::U16 => device.build_input_stream(&stream_config, move |data: &[u16], _| {
-            process_and_write_data(data, &mut resampler, &mut audio_buffer, &stdout, RESAMPLER_CHUNK_SIZE)
+            process_and_write_data(data, &mut resampler, &mut audio_buffer, &stdout, RESAMPLER_CHUNK_SIZE, &mut vad_state, channels, &events_tx, &wav_writer_for_stream)
         }, err_fn, None)?,
         format => return Err(anyhow!("[audio-recorder] Unsupported sample format {}", format))
     };
 
-    Ok(stream)
-}
\ No newline at end of file
+    Ok((stream, wav_writer, stream_config_message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_types(events: &[serde_json::Value]) -> Vec<&str> {
+        events.iter().filter_map(|e| e["type"].as_str()).collect()
+    }
+
+    #[test]
+    fn zero_crossing_rate_is_zero_for_constant_signal() {
+        let frame = vec![1.0; 30];
+        assert_eq!(zero_crossing_rate(&frame), 0.0);
+    }
+
+    #[test]
+    fn zero_crossing_rate_is_one_for_alternating_signal() {
+        let frame: Vec<f32> = (0..30).map(|i| if i % 2 == 0 { 1.0 } else { -1.0 }).collect();
+        assert_eq!(zero_crossing_rate(&frame), 1.0);
+    }
+
+    #[test]
+    fn zero_crossing_rate_is_zero_for_short_frames() {
+        assert_eq!(zero_crossing_rate(&[0.5]), 0.0);
+        assert_eq!(zero_crossing_rate(&[]), 0.0);
+    }
+
+    #[test]
+    fn loud_frame_after_silence_emits_speech_start() {
+        let config = VadConfig { k: 3.0 };
+        let mut vad = VadState::new(&config, 1000);
+
+        // Settle the adaptive noise floor on one quiet 30 ms frame first; it
+        // lands in the pre-roll buffer rather than being streamed.
+        let (out, events) = vad.process(&vec![0.0; 30]);
+        assert!(events.is_empty());
+        assert!(out.is_empty());
+
+        let loud_frame = vec![1.0; 30];
+        let (out, events) = vad.process(&loud_frame);
+        assert_eq!(event_types(&events), vec!["speech-start"]);
+        // Speech start flushes the pre-roll ahead of the loud frame.
+        let mut expected = vec![0.0; 30];
+        expected.extend_from_slice(&loud_frame);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn speech_end_fires_once_hangover_elapses() {
+        let config = VadConfig { k: 3.0 };
+        let mut vad = VadState::new(&config, 1000);
+
+        vad.process(&vec![0.0; 30]);
+        let (_, start_events) = vad.process(&vec![1.0; 30]);
+        assert_eq!(event_types(&start_events), vec!["speech-start"]);
+
+        // Feed enough silent frames to exhaust the hangover window.
+        let mut saw_speech_end = false;
+        for _ in 0..32 {
+            let (_, events) = vad.process(&vec![0.0; 30]);
+            if event_types(&events).contains(&"speech-end") {
+                saw_speech_end = true;
+                break;
+            }
+        }
+        assert!(saw_speech_end, "expected a speech-end event once the hangover window elapsed");
+    }
+}
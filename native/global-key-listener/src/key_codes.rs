@@ -0,0 +1,159 @@
+use rdev::Key;
+
+/// Maps a key to the raw platform keycode consumers may want alongside its
+/// symbolic name. Falls back to `0` for keys without a well-known code.
+pub fn key_to_code(key: &Key) -> u32 {
+    match key {
+        Key::Alt => 56,
+        Key::AltGr => 100,
+        Key::Backspace => 14,
+        Key::CapsLock => 58,
+        Key::ControlLeft => 29,
+        Key::ControlRight => 97,
+        Key::Delete => 111,
+        Key::DownArrow => 108,
+        Key::End => 107,
+        Key::Escape => 1,
+        Key::F1 => 59,
+        Key::F2 => 60,
+        Key::F3 => 61,
+        Key::F4 => 62,
+        Key::F5 => 63,
+        Key::F6 => 64,
+        Key::F7 => 65,
+        Key::F8 => 66,
+        Key::F9 => 67,
+        Key::F10 => 68,
+        Key::F11 => 87,
+        Key::F12 => 88,
+        Key::Home => 102,
+        Key::LeftArrow => 105,
+        Key::MetaLeft => 125,
+        Key::MetaRight => 126,
+        Key::PageDown => 109,
+        Key::PageUp => 104,
+        Key::Return => 28,
+        Key::RightArrow => 106,
+        Key::ShiftLeft => 42,
+        Key::ShiftRight => 54,
+        Key::Space => 57,
+        Key::Tab => 15,
+        Key::UpArrow => 103,
+        Key::KeyA => 30,
+        Key::KeyB => 48,
+        Key::KeyC => 46,
+        Key::KeyD => 32,
+        Key::KeyE => 18,
+        Key::KeyF => 33,
+        Key::KeyG => 34,
+        Key::KeyH => 35,
+        Key::KeyI => 23,
+        Key::KeyJ => 36,
+        Key::KeyK => 37,
+        Key::KeyL => 38,
+        Key::KeyM => 50,
+        Key::KeyN => 49,
+        Key::KeyO => 24,
+        Key::KeyP => 25,
+        Key::KeyQ => 16,
+        Key::KeyR => 19,
+        Key::KeyS => 31,
+        Key::KeyT => 20,
+        Key::KeyU => 22,
+        Key::KeyV => 47,
+        Key::KeyW => 17,
+        Key::KeyX => 45,
+        Key::KeyY => 21,
+        Key::KeyZ => 44,
+        Key::Num0 => 11,
+        Key::Num1 => 2,
+        Key::Num2 => 3,
+        Key::Num3 => 4,
+        Key::Num4 => 5,
+        Key::Num5 => 6,
+        Key::Num6 => 7,
+        Key::Num7 => 8,
+        Key::Num8 => 9,
+        Key::Num9 => 10,
+        _ => 0,
+    }
+}
+
+/// Parses the `{:?}` name of an `rdev::Key` back into a `Key`, so remap
+/// targets can be specified as plain strings over the JSON command protocol.
+pub fn key_from_name(name: &str) -> Option<Key> {
+    Some(match name {
+        "Alt" => Key::Alt,
+        "AltGr" => Key::AltGr,
+        "Backspace" => Key::Backspace,
+        "CapsLock" => Key::CapsLock,
+        "ControlLeft" => Key::ControlLeft,
+        "ControlRight" => Key::ControlRight,
+        "Delete" => Key::Delete,
+        "DownArrow" => Key::DownArrow,
+        "End" => Key::End,
+        "Escape" => Key::Escape,
+        "F1" => Key::F1,
+        "F2" => Key::F2,
+        "F3" => Key::F3,
+        "F4" => Key::F4,
+        "F5" => Key::F5,
+        "F6" => Key::F6,
+        "F7" => Key::F7,
+        "F8" => Key::F8,
+        "F9" => Key::F9,
+        "F10" => Key::F10,
+        "F11" => Key::F11,
+        "F12" => Key::F12,
+        "Home" => Key::Home,
+        "LeftArrow" => Key::LeftArrow,
+        "MetaLeft" => Key::MetaLeft,
+        "MetaRight" => Key::MetaRight,
+        "PageDown" => Key::PageDown,
+        "PageUp" => Key::PageUp,
+        "Return" => Key::Return,
+        "RightArrow" => Key::RightArrow,
+        "ShiftLeft" => Key::ShiftLeft,
+        "ShiftRight" => Key::ShiftRight,
+        "Space" => Key::Space,
+        "Tab" => Key::Tab,
+        "UpArrow" => Key::UpArrow,
+        "KeyA" => Key::KeyA,
+        "KeyB" => Key::KeyB,
+        "KeyC" => Key::KeyC,
+        "KeyD" => Key::KeyD,
+        "KeyE" => Key::KeyE,
+        "KeyF" => Key::KeyF,
+        "KeyG" => Key::KeyG,
+        "KeyH" => Key::KeyH,
+        "KeyI" => Key::KeyI,
+        "KeyJ" => Key::KeyJ,
+        "KeyK" => Key::KeyK,
+        "KeyL" => Key::KeyL,
+        "KeyM" => Key::KeyM,
+        "KeyN" => Key::KeyN,
+        "KeyO" => Key::KeyO,
+        "KeyP" => Key::KeyP,
+        "KeyQ" => Key::KeyQ,
+        "KeyR" => Key::KeyR,
+        "KeyS" => Key::KeyS,
+        "KeyT" => Key::KeyT,
+        "KeyU" => Key::KeyU,
+        "KeyV" => Key::KeyV,
+        "KeyW" => Key::KeyW,
+        "KeyX" => Key::KeyX,
+        "KeyY" => Key::KeyY,
+        "KeyZ" => Key::KeyZ,
+        "Num0" => Key::Num0,
+        "Num1" => Key::Num1,
+        "Num2" => Key::Num2,
+        "Num3" => Key::Num3,
+        "Num4" => Key::Num4,
+        "Num5" => Key::Num5,
+        "Num6" => Key::Num6,
+        "Num7" => Key::Num7,
+        "Num8" => Key::Num8,
+        "Num9" => Key::Num9,
+        _ => return None,
+    })
+}
@@ -2,7 +2,9 @@ use chrono::Utc;
 use rdev::{grab, Event, EventType, Key};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, BufRead, Write};
+use std::sync::{Arc, RwLock};
 use std::thread;
 
 mod key_codes;
@@ -16,19 +18,45 @@ enum Command {
     Unblock { key: String },
     #[serde(rename = "get_blocked")]
     GetBlocked,
+    #[serde(rename = "remap")]
+    Remap { key: String, action: RemapAction },
+    #[serde(rename = "chord")]
+    Chord { key: String, modifiers: Vec<String> },
 }
 
-// Global state for blocked keys
-static mut BLOCKED_KEYS: Vec<String> = Vec::new();
+/// What to do with a remapped key: drop it, let it through unchanged, or
+/// substitute a different key in its place.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum RemapAction {
+    Suppress,
+    Passthrough,
+    Emit { key: String },
+}
+
+/// The blocking/remap/chord rules, shared between the stdin command thread
+/// and the `rdev::grab` callback. Replaces the old `static mut BLOCKED_KEYS`,
+/// which was read and written from two threads with no synchronization.
+#[derive(Debug, Default)]
+struct BlockConfig {
+    blocked: HashSet<String>,
+    remaps: HashMap<String, RemapAction>,
+    /// Key name -> modifier key names that must all be held for the key to
+    /// be suppressed. Lets callers block e.g. only `Cmd+Q`, not `Q` alone.
+    chords: HashMap<String, HashSet<String>>,
+}
 
 fn main() {
+    let config = Arc::new(RwLock::new(BlockConfig::default()));
+
     // Spawn a thread to read commands from stdin
-    thread::spawn(|| {
+    let command_config = Arc::clone(&config);
+    thread::spawn(move || {
         let stdin = io::stdin();
         for line in stdin.lock().lines() {
             if let Ok(line) = line {
                 match serde_json::from_str::<Command>(&line) {
-                    Ok(command) => handle_command(command),
+                    Ok(command) => handle_command(command, &command_config),
                     Err(e) => eprintln!("Error parsing command: {}", e),
                 }
             }
@@ -36,60 +64,129 @@ fn main() {
     });
 
     // Start grabbing events
-    if let Err(error) = grab(callback) {
+    let mut held_modifiers: HashSet<String> = HashSet::new();
+    if let Err(error) = grab(move |event| callback(event, &config, &mut held_modifiers)) {
         eprintln!("Error: {:?}", error);
     }
 }
 
-fn handle_command(command: Command) {
+fn handle_command(command: Command, config: &Arc<RwLock<BlockConfig>>) {
     match command {
-        Command::Block { keys } => unsafe {
-            BLOCKED_KEYS = keys;
-        },
-        Command::Unblock { key } => unsafe {
-            BLOCKED_KEYS.retain(|k| k != &key);
-        },
-        Command::GetBlocked => unsafe {
+        Command::Block { keys } => {
+            let mut config = config.write().unwrap();
+            config.blocked = keys.into_iter().collect();
+        }
+        Command::Unblock { key } => {
+            let mut config = config.write().unwrap();
+            config.blocked.remove(&key);
+        }
+        Command::GetBlocked => {
+            let config = config.read().unwrap();
             println!(
                 "{}",
                 json!({
                     "type": "blocked_keys",
-                    "keys": BLOCKED_KEYS
+                    "keys": config.blocked.iter().collect::<Vec<_>>()
                 })
             );
-        },
+        }
+        Command::Remap { key, action } => {
+            let mut config = config.write().unwrap();
+            config.remaps.insert(key, action);
+        }
+        Command::Chord { key, modifiers } => {
+            let mut config = config.write().unwrap();
+            config.chords.insert(key, modifiers.into_iter().collect());
+        }
     }
     io::stdout().flush().unwrap();
 }
 
-fn callback(event: Event) -> Option<Event> {
-    match event.event_type {
+fn callback(event: Event, config: &Arc<RwLock<BlockConfig>>, held_modifiers: &mut HashSet<String>) -> Option<Event> {
+    match &event.event_type {
         EventType::KeyPress(key) => {
+            let key = *key;
             let key_name = format!("{:?}", key);
-            let should_block = unsafe { BLOCKED_KEYS.contains(&key_name) };
-
-            output_event("keydown", &key);
-
-            match should_block {
-                true => None,
-                false => Some(event),
+            if is_modifier(key) {
+                held_modifiers.insert(key_name.clone());
             }
+            output_event("keydown", &key);
+            apply_rules(event, &key_name, config, held_modifiers)
         }
         EventType::KeyRelease(key) => {
+            let key = *key;
             let key_name = format!("{:?}", key);
-            let should_block = unsafe { BLOCKED_KEYS.contains(&key_name) };
-
+            if is_modifier(key) {
+                held_modifiers.remove(&key_name);
+            }
             output_event("keyup", &key);
+            apply_rules(event, &key_name, config, held_modifiers)
+        }
+        _ => Some(event), // Allow all other events
+    }
+}
+
+/// Decides the fate of a key event: chord-suppressed, remapped (suppressed /
+/// passed through / substituted), plain-blocked, or allowed through as-is.
+fn apply_rules(
+    event: Event,
+    key_name: &str,
+    config: &Arc<RwLock<BlockConfig>>,
+    held_modifiers: &HashSet<String>,
+) -> Option<Event> {
+    let config = match config.read() {
+        Ok(config) => config,
+        Err(_) => return Some(event),
+    };
 
-            match should_block {
-                true => None,
-                false => Some(event),
+    if let Some(required_modifiers) = config.chords.get(key_name) {
+        if !required_modifiers.is_empty() && required_modifiers.is_subset(held_modifiers) {
+            return None;
+        }
+    }
+
+    match config.remaps.get(key_name) {
+        Some(RemapAction::Suppress) => None,
+        Some(RemapAction::Passthrough) => Some(event),
+        Some(RemapAction::Emit { key: target }) => match key_codes::key_from_name(target) {
+            Some(target_key) => Some(Event {
+                event_type: retarget(&event.event_type, target_key),
+                ..event
+            }),
+            None => Some(event),
+        },
+        None => {
+            if config.blocked.contains(key_name) {
+                None
+            } else {
+                Some(event)
             }
         }
-        _ => Some(event), // Allow all other events
     }
 }
 
+fn retarget(event_type: &EventType, key: Key) -> EventType {
+    match event_type {
+        EventType::KeyPress(_) => EventType::KeyPress(key),
+        EventType::KeyRelease(_) => EventType::KeyRelease(key),
+        other => other.clone(),
+    }
+}
+
+fn is_modifier(key: Key) -> bool {
+    matches!(
+        key,
+        Key::ShiftLeft
+            | Key::ShiftRight
+            | Key::ControlLeft
+            | Key::ControlRight
+            | Key::Alt
+            | Key::AltGr
+            | Key::MetaLeft
+            | Key::MetaRight
+    )
+}
+
 fn output_event(event_type: &str, key: &Key) {
     let timestamp = Utc::now().to_rfc3339();
     let key_name = format!("{:?}", key);
@@ -104,3 +201,88 @@ fn output_event(event_type: &str, key: &Key) {
     println!("{}", event_json);
     io::stdout().flush().unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    fn key_press(key: Key) -> Event {
+        Event {
+            event_type: EventType::KeyPress(key),
+            time: SystemTime::now(),
+            name: None,
+        }
+    }
+
+    #[test]
+    fn blocked_key_is_suppressed() {
+        let config = Arc::new(RwLock::new(BlockConfig {
+            blocked: HashSet::from(["KeyA".to_string()]),
+            ..Default::default()
+        }));
+        let held = HashSet::new();
+
+        let result = apply_rules(key_press(Key::KeyA), "KeyA", &config, &held);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn unblocked_key_passes_through() {
+        let config = Arc::new(RwLock::new(BlockConfig::default()));
+        let held = HashSet::new();
+
+        let result = apply_rules(key_press(Key::KeyA), "KeyA", &config, &held);
+        assert!(result.is_some());
+    }
+
+    #[test]
+    fn remap_suppress_drops_key_not_in_block_list() {
+        let mut remaps = HashMap::new();
+        remaps.insert("KeyA".to_string(), RemapAction::Suppress);
+        let config = Arc::new(RwLock::new(BlockConfig {
+            remaps,
+            ..Default::default()
+        }));
+        let held = HashSet::new();
+
+        let result = apply_rules(key_press(Key::KeyA), "KeyA", &config, &held);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn remap_emit_retargets_the_key() {
+        let mut remaps = HashMap::new();
+        remaps.insert(
+            "KeyA".to_string(),
+            RemapAction::Emit { key: "KeyB".to_string() },
+        );
+        let config = Arc::new(RwLock::new(BlockConfig {
+            remaps,
+            ..Default::default()
+        }));
+        let held = HashSet::new();
+
+        let result = apply_rules(key_press(Key::KeyA), "KeyA", &config, &held);
+        match result.map(|e| e.event_type) {
+            Some(EventType::KeyPress(Key::KeyB)) => {}
+            other => panic!("expected remapped KeyPress(KeyB), got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn chord_is_suppressed_only_when_required_modifiers_are_held() {
+        let mut chords = HashMap::new();
+        chords.insert("KeyQ".to_string(), HashSet::from(["MetaLeft".to_string()]));
+        let config = Arc::new(RwLock::new(BlockConfig {
+            chords,
+            ..Default::default()
+        }));
+
+        let no_modifiers = HashSet::new();
+        assert!(apply_rules(key_press(Key::KeyQ), "KeyQ", &config, &no_modifiers).is_some());
+
+        let with_meta = HashSet::from(["MetaLeft".to_string()]);
+        assert!(apply_rules(key_press(Key::KeyQ), "KeyQ", &config, &with_meta).is_none());
+    }
+}
@@ -1,5 +1,6 @@
-use clap::Parser;
-use enigo::{Enigo, Keyboard, Settings};
+use clap::{ArgGroup, Parser};
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::error::Error;
 use std::process;
 use std::thread;
 use std::time::Duration;
@@ -8,8 +9,9 @@ use std::time::Duration;
 #[command(name = "text-writer")]
 #[command(about = "A cross-platform text typing utility")]
 #[command(version = "0.1.0")]
+#[command(group(ArgGroup::new("mode").args(["keys", "paste"])))]
 struct Args {
-    #[arg(help = "Text to type")]
+    #[arg(help = "Text to type, or a chord spec (e.g. \"ctrl+shift+v\") with --keys")]
     text: String,
 
     #[arg(
@@ -27,6 +29,18 @@ struct Args {
         help = "Delay between characters (milliseconds)"
     )]
     char_delay: u64,
+
+    #[arg(
+        long,
+        help = "Interpret `text` as a key combination (e.g. \"ctrl+shift+v\") instead of literal text"
+    )]
+    keys: bool,
+
+    #[arg(
+        long,
+        help = "Set the clipboard to `text` and paste it via the platform shortcut, instead of typing it character-by-character"
+    )]
+    paste: bool,
 }
 
 fn main() {
@@ -49,7 +63,17 @@ fn main() {
         }
     };
 
-    if args.char_delay > 0 {
+    if args.keys {
+        if let Err(e) = type_chord(&mut enigo, &args.text) {
+            eprintln!("Error typing key combination '{}': {}", args.text, e);
+            process::exit(1);
+        }
+    } else if args.paste {
+        if let Err(e) = paste_text(&mut enigo, &args.text) {
+            eprintln!("Error pasting text: {}", e);
+            process::exit(1);
+        }
+    } else if args.char_delay > 0 {
         for ch in args.text.chars() {
             if let Err(e) = enigo.text(&ch.to_string()) {
                 eprintln!("Error typing character '{}': {}", ch, e);
@@ -57,10 +81,176 @@ fn main() {
             }
             thread::sleep(Duration::from_millis(args.char_delay));
         }
+    } else if let Err(e) = enigo.text(&args.text) {
+        eprintln!("Error typing text: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Parses a chord spec like `ctrl+shift+v` or `cmd+a` into its modifier keys
+/// (in the order given) and final key.
+fn parse_chord(spec: &str) -> Result<(Vec<Key>, Key), String> {
+    let parts: Vec<&str> = spec
+        .split('+')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect();
+
+    let (modifier_names, key_name) = parts
+        .split_last()
+        .ok_or_else(|| format!("empty key combination '{}'", spec))?;
+
+    let modifiers: Vec<Key> = modifier_names
+        .iter()
+        .map(|name| key_from_name(name).ok_or_else(|| format!("unknown modifier '{}' in '{}'", name, spec)))
+        .collect::<Result<_, _>>()?;
+    let key = key_from_name(key_name).ok_or_else(|| format!("unknown key '{}' in '{}'", key_name, spec))?;
+
+    Ok((modifiers, key))
+}
+
+/// Parses a chord spec and plays it back as press-modifiers, tap-key,
+/// release-modifiers-in-reverse.
+fn type_chord(enigo: &mut Enigo, spec: &str) -> Result<(), Box<dyn Error>> {
+    let (modifiers, key) = parse_chord(spec)?;
+
+    for modifier in &modifiers {
+        enigo.key(*modifier, Direction::Press)?;
+    }
+    enigo.key(key, Direction::Click)?;
+    for modifier in modifiers.iter().rev() {
+        enigo.key(*modifier, Direction::Release)?;
+    }
+
+    Ok(())
+}
+
+/// Sets the clipboard to `text` and issues the platform paste shortcut, for
+/// fast, reliable insertion of large or Unicode-heavy text that per-character
+/// typing mangles.
+fn paste_text(enigo: &mut Enigo, text: &str) -> Result<(), Box<dyn Error>> {
+    #[cfg(target_os = "linux")]
+    let _clipboard_server = spawn_x11_clipboard_server(text)?;
+    #[cfg(not(target_os = "linux"))]
+    {
+        let mut clipboard = arboard::Clipboard::new()?;
+        clipboard.set_text(text.to_string())?;
+    }
+
+    let paste_modifier = if cfg!(target_os = "macos") {
+        Key::Meta
     } else {
-        if let Err(e) = enigo.text(&args.text) {
-            eprintln!("Error typing text: {}", e);
-            process::exit(1);
+        Key::Control
+    };
+
+    enigo.key(paste_modifier, Direction::Press)?;
+    enigo.key(Key::Unicode('v'), Direction::Click)?;
+    enigo.key(paste_modifier, Direction::Release)?;
+
+    // Give the target application a moment to actually issue its paste
+    // request before we return and the process (and with it, the clipboard
+    // server thread above) exits.
+    #[cfg(target_os = "linux")]
+    thread::sleep(Duration::from_millis(150));
+
+    Ok(())
+}
+
+/// On X11, arboard only serves clipboard content to other applications while
+/// its clipboard context stays alive -- setting the text and exiting
+/// immediately (as a plain `set_text` + process exit would) races the target
+/// application's paste request and can deliver stale or empty content.
+/// Hands ownership to a background thread that blocks in `set().wait()`
+/// until another application takes the clipboard, keeping the content alive
+/// long enough for the paste issued right after this returns to land. Not
+/// needed on macOS/Windows, where the OS clipboard manager owns the content
+/// independently of the process that set it.
+#[cfg(target_os = "linux")]
+fn spawn_x11_clipboard_server(text: &str) -> Result<thread::JoinHandle<()>, Box<dyn Error>> {
+    use arboard::SetExtLinux;
+
+    let text = text.to_string();
+    let handle = thread::spawn(move || {
+        if let Ok(mut clipboard) = arboard::Clipboard::new() {
+            let _ = clipboard.set().wait().text(text);
         }
+    });
+
+    Ok(handle)
+}
+
+/// Maps a key or modifier name from a chord spec to its `enigo::Key`. Named
+/// modifiers and common control keys are recognized explicitly; any other
+/// single character is typed via `Key::Unicode`.
+fn key_from_name(name: &str) -> Option<Key> {
+    let lower = name.to_lowercase();
+    Some(match lower.as_str() {
+        "ctrl" | "control" => Key::Control,
+        "cmd" | "command" | "meta" | "win" | "windows" | "super" => Key::Meta,
+        "alt" | "option" => Key::Alt,
+        "shift" => Key::Shift,
+        "enter" | "return" => Key::Return,
+        "tab" => Key::Tab,
+        "space" => Key::Space,
+        "esc" | "escape" => Key::Escape,
+        "backspace" => Key::Backspace,
+        "delete" | "del" => Key::Delete,
+        "up" => Key::UpArrow,
+        "down" => Key::DownArrow,
+        "left" => Key::LeftArrow,
+        "right" => Key::RightArrow,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        _ if lower.chars().count() == 1 => Key::Unicode(lower.chars().next().unwrap()),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_from_name_recognizes_modifiers_case_insensitively() {
+        assert!(matches!(key_from_name("Ctrl"), Some(Key::Control)));
+        assert!(matches!(key_from_name("CMD"), Some(Key::Meta)));
+        assert!(matches!(key_from_name("Shift"), Some(Key::Shift)));
+    }
+
+    #[test]
+    fn key_from_name_falls_back_to_unicode_for_single_chars() {
+        assert!(matches!(key_from_name("v"), Some(Key::Unicode('v'))));
+        assert!(matches!(key_from_name("V"), Some(Key::Unicode('v'))));
+    }
+
+    #[test]
+    fn key_from_name_rejects_unknown_multi_char_names() {
+        assert!(key_from_name("nonsense").is_none());
+    }
+
+    #[test]
+    fn parse_chord_splits_modifiers_and_key_in_order() {
+        let (modifiers, key) = parse_chord("ctrl+shift+v").unwrap();
+        assert!(matches!(modifiers.as_slice(), [Key::Control, Key::Shift]));
+        assert!(matches!(key, Key::Unicode('v')));
+    }
+
+    #[test]
+    fn parse_chord_accepts_a_single_key_with_no_modifiers() {
+        let (modifiers, key) = parse_chord("a").unwrap();
+        assert!(modifiers.is_empty());
+        assert!(matches!(key, Key::Unicode('a')));
+    }
+
+    #[test]
+    fn parse_chord_rejects_unknown_modifier() {
+        assert!(parse_chord("frobnicate+v").is_err());
+    }
+
+    #[test]
+    fn parse_chord_rejects_empty_spec() {
+        assert!(parse_chord("").is_err());
     }
 }